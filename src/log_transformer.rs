@@ -1,10 +1,13 @@
-use std::collections::HashSet;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
 
 use chrono::{DateTime, Local, NaiveDateTime, Utc};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use regex::RegexSet;
 use serde_json::{Map, Value};
-use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, NoColor, WriteColor};
 
 /// Log messages longer than MULTILINE_MESSAGE_THRESHOLD will have their fields put on a separate line
 const MULTILINE_MESSAGE_THRESHOLD: usize = 120;
@@ -43,6 +46,16 @@ pub struct Config {
     #[clap(short, long)]
     filter_levels: Option<Vec<String>>,
 
+    /// Only show logs at or above this severity (trace/debug < info/notice < warning < error/critical/fatal).
+    /// Composes with `filter_levels`; both must pass for an entry to be shown
+    #[clap(long)]
+    min_level: Option<String>,
+
+    /// The severity rank assigned to levels that aren't recognized, used by `min_level`.
+    /// Defaults to always showing unrecognized levels
+    #[clap(long, default_value_t = i64::MAX)]
+    unknown_level_rank: i64,
+
     /// The number of empty lines printed after formatted logs
     #[clap(short, long, default_value = "0")]
     spacing: i64,
@@ -70,7 +83,237 @@ pub struct Config {
     #[clap(long)]
     jql_filter: Option<String>,
 
+    /// A chrono format string used to parse string timestamps that aren't valid RFC3339,
+    /// and to render the `[...]` timestamp prefix
+    #[clap(long)]
     timestamp_format: Option<String>,
+
+    /// Only show entries whose grep target matches at least one of these regexes. Repeatable
+    #[clap(long)]
+    grep: Option<Vec<String>>,
+
+    /// Hide entries whose grep target matches any of these regexes. Repeatable
+    #[clap(long)]
+    grep_exclude: Option<Vec<String>>,
+
+    /// Match --grep/--grep-exclude against the raw JSON line instead of just the message field
+    #[clap(long)]
+    grep_whole_line: bool,
+
+    /// Also write formatted (color-stripped) output to this file, rotating it once it grows
+    /// past --max-file-size
+    #[clap(long)]
+    output_file: Option<String>,
+
+    /// The maximum size in bytes of --output-file before it's rotated to a timestamped suffix
+    #[clap(long, default_value_t = 10 * 1024 * 1024)]
+    max_file_size: u64,
+
+    /// A comma-separated color theme spec, e.g.
+    /// "level:error=red,level:warning=yellow,key=cyan,value=white,timestamp=magenta".
+    /// Colors accept names (black/red/green/yellow/blue/magenta/cyan/white),
+    /// `ansi256:<n>`, or `rgb:<r>,<g>,<b>`. Unspecified parts keep the default colors
+    #[clap(long)]
+    theme: Option<String>,
+
+    /// The output sink format. Defaults to "pretty" (human-readable); "json" re-emits
+    /// the filtered/transformed record as newline-delimited JSON
+    #[clap(long, value_enum)]
+    output: Option<OutputFormat>,
+}
+
+/// The sink format used by `transform_and_print`
+#[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Pretty,
+    Json,
+}
+
+/// Resolves the colors used for level text, field keys/values, and the timestamp prefix.
+/// Falls back to the tool's defaults for anything not covered by a `--theme` spec
+struct Theme {
+    level_colors: HashMap<String, Color>,
+    key_color: Color,
+    value_color: Color,
+    timestamp_color: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            level_colors: HashMap::new(),
+            key_color: Color::Green,
+            value_color: Color::Black,
+            timestamp_color: Color::Magenta,
+        }
+    }
+}
+
+impl Theme {
+    fn parse(spec: &str) -> Theme {
+        let mut theme = Theme::default();
+
+        // Commas also separate the channels of `rgb:<r>,<g>,<b>`, so a non-empty piece
+        // without '=' continues the previous entry instead of starting a new one
+        let mut entries: Vec<String> = Vec::new();
+        for piece in spec.split(',') {
+            match entries.last_mut() {
+                Some(last) if !piece.contains('=') && !piece.trim().is_empty() => {
+                    last.push(',');
+                    last.push_str(piece);
+                }
+                _ => entries.push(piece.to_string()),
+            }
+        }
+
+        for entry in &entries {
+            let entry = entry.trim();
+
+            if entry.is_empty() {
+                continue;
+            }
+
+            let mut parts = entry.splitn(2, '=');
+            let key = match parts.next() {
+                Some(k) => k.trim(),
+                None => continue,
+            };
+            let value = match parts.next() {
+                Some(v) => v.trim(),
+                None => continue,
+            };
+
+            let color = match parse_color(value) {
+                Some(color) => color,
+                None => continue,
+            };
+
+            match key.strip_prefix("level:") {
+                Some(level) => {
+                    theme.level_colors.insert(level.trim().to_lowercase(), color);
+                }
+                None => match key {
+                    "key" => theme.key_color = color,
+                    "value" => theme.value_color = color,
+                    "timestamp" => theme.timestamp_color = color,
+                    _ => {}
+                },
+            }
+        }
+
+        theme
+    }
+
+    /// The color used for the level tag, falling back to the tool's
+    /// built-in family-based default (trace/debug, info/notice, warning, error and friends)
+    fn level_color(&self, level: &str) -> Color {
+        self.level_colors
+            .get(level.trim().to_lowercase().as_str())
+            .copied()
+            .unwrap_or_else(|| default_level_color(level))
+    }
+}
+
+/// Color names accept "black"/"red"/.../"white", `ansi256:<n>`, or `rgb:<r>,<g>,<b>`
+fn parse_color(spec: &str) -> Option<Color> {
+    let spec = spec.trim();
+
+    if let Some(n) = spec.strip_prefix("ansi256:") {
+        return n.trim().parse::<u8>().ok().map(Color::Ansi256);
+    }
+
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let channels: Vec<&str> = rgb.split(',').collect();
+
+        if let [r, g, b] = channels[..] {
+            let r = r.trim().parse::<u8>().ok()?;
+            let g = g.trim().parse::<u8>().ok()?;
+            let b = b.trim().parse::<u8>().ok()?;
+
+            return Some(Color::Rgb(r, g, b));
+        }
+
+        return None;
+    }
+
+    match spec.to_lowercase().as_str() {
+        "black" => Some(Color::Black),
+        "red" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" => Some(Color::Yellow),
+        "blue" => Some(Color::Blue),
+        "magenta" => Some(Color::Magenta),
+        "cyan" => Some(Color::Cyan),
+        "white" => Some(Color::White),
+        _ => None,
+    }
+}
+
+fn default_level_color(level: &str) -> Color {
+    match level.trim().to_lowercase().as_str() {
+        "trace" | "debug" => Color::Black,
+        "info" | "notice" => Color::Blue,
+        "warning" => Color::Yellow,
+        "error" | "err" | "critical" | "crit" | "fatal" | "emerg" | "emergency" | "alert" => Color::Red,
+        _ => Color::Black,
+    }
+}
+
+/// Writes formatted log lines to a file, renaming it to a timestamped, uniquely
+/// numbered suffix and starting a fresh file once `max_size` bytes would be exceeded.
+///
+/// Callers must pass one whole record per `write`/`write_all` call (never fragments of
+/// a record) so the rotation check below can't split a single record across two files.
+struct RotatingFileWriter {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    bytes_written: u64,
+    rotation_count: u64,
+}
+
+impl RotatingFileWriter {
+    fn new(path: PathBuf, max_size: u64) -> io::Result<RotatingFileWriter> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let bytes_written = file.metadata()?.len();
+
+        Ok(RotatingFileWriter { path, max_size, file, bytes_written, rotation_count: 0 })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.rotation_count += 1;
+
+        let rotated_path = self.path.with_file_name(format!(
+            "{}.{}-{:06}",
+            self.path.file_name().and_then(|n| n.to_str()).unwrap_or("output.log"),
+            Local::now().format("%Y%m%d-%H%M%S%.f"),
+            self.rotation_count,
+        ));
+
+        fs::rename(&self.path, rotated_path)?;
+
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.bytes_written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.bytes_written > 0 && self.bytes_written + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(buf)?;
+        self.bytes_written += buf.len() as u64;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
 }
 
 pub struct LogTransformer {
@@ -81,12 +324,40 @@ pub struct LogTransformer {
     separator: String,
     hide_extra_fields: bool,
     filter_levels: HashSet<String>,
+    min_level: Option<i64>,
+    unknown_level_rank: i64,
     disable_colors: bool,
     spacing: i64,
     hide_non_json: bool,
     multiline_fields: bool,
     jql: Option<String>,
     jql_filter: Option<String>,
+    timestamp_format: Option<String>,
+    grep: Option<RegexSet>,
+    grep_exclude: Option<RegexSet>,
+    grep_whole_line: bool,
+    output_file: Option<RotatingFileWriter>,
+    theme: Theme,
+    output_json: bool,
+}
+
+/// Maps a recognized level family to its severity ordinal (higher is more severe), using
+/// the same families as the color match arm. Returns `None` outside that known set
+fn known_level_rank(level: &str) -> Option<i64> {
+    match level.trim().to_lowercase().as_str() {
+        "trace" | "debug" => Some(0),
+        "info" | "notice" => Some(1),
+        "warning" => Some(2),
+        "error" | "err" | "critical" | "crit" | "fatal" | "emerg" | "emergency" | "alert" => Some(3),
+        _ => None,
+    }
+}
+
+/// Maps a level string to a severity ordinal (higher is more severe). Unknown levels
+/// map to `unknown_rank` rather than being rejected, since log records commonly carry
+/// levels outside the recognized set
+fn level_rank(level: &str, unknown_rank: i64) -> i64 {
+    known_level_rank(level).unwrap_or(unknown_rank)
 }
 
 impl LogTransformer {
@@ -101,6 +372,44 @@ impl LogTransformer {
             None => HashSet::new(),
         };
 
+        // Validated against the known level families rather than reusing level_rank's
+        // "unknown level" fallback: if a typo like "warn" silently mapped to i64::MAX,
+        // every real record (rank <= 3) would be filtered out with no error at all
+        let min_level = config.min_level.as_ref().map(|level| {
+            known_level_rank(level).unwrap_or_else(|| {
+                panic!(
+                    "--min-level {:?} is not a recognized level; expected one of: \
+                     trace, debug, info, notice, warning, error, err, critical, crit, \
+                     fatal, emerg, emergency, alert",
+                    level
+                )
+            })
+        });
+
+        let grep = match &config.grep {
+            Some(patterns) if !patterns.is_empty() => {
+                Some(RegexSet::new(patterns).expect("invalid --grep regex"))
+            }
+            _ => None,
+        };
+
+        let grep_exclude = match &config.grep_exclude {
+            Some(patterns) if !patterns.is_empty() => {
+                Some(RegexSet::new(patterns).expect("invalid --grep-exclude regex"))
+            }
+            _ => None,
+        };
+
+        let output_file = config.output_file.as_ref().map(|path| {
+            RotatingFileWriter::new(PathBuf::from(path), config.max_file_size)
+                .expect("could not open --output-file")
+        });
+
+        let theme = match &config.theme {
+            Some(spec) => Theme::parse(spec),
+            None => Theme::default(),
+        };
+
         excluded_fields.insert(config.message_field_name.clone());
         excluded_fields.insert(config.level_field_name.clone());
         excluded_fields.insert(config.timestamp_field_name.clone());
@@ -118,11 +427,125 @@ impl LogTransformer {
             filter_levels,
             multiline_fields: config.multiline_fields,
             jql: config.jql,
-            jql_filter: config.jql_filter
+            jql_filter: config.jql_filter,
+            timestamp_format: config.timestamp_format,
+            min_level,
+            unknown_level_rank: config.unknown_level_rank,
+            grep,
+            grep_exclude,
+            grep_whole_line: config.grep_whole_line,
+            output_file,
+            theme,
+            output_json: matches!(config.output, Some(OutputFormat::Json)),
+        }
+    }
+
+    /// Reads the configured timestamp field and parses it into a `DateTime<Local>`.
+    ///
+    /// Numeric values are treated as a Unix epoch, auto-detecting seconds vs.
+    /// millis vs. micros by magnitude. String values are tried as RFC3339 first,
+    /// then against the user-supplied `timestamp_format`. Returns `None` when the
+    /// field is missing or couldn't be parsed, rather than panicking.
+    fn resolve_timestamp(&self, obj: &Map<String, Value>) -> Option<DateTime<Local>> {
+        let value = obj.get(&self.timestamp_field)?;
+
+        if let Some(n) = value.as_f64() {
+            let secs = if n >= 1e14 {
+                n / 1_000_000.0
+            } else if n >= 1e11 {
+                n / 1_000.0
+            } else {
+                n
+            };
+
+            let whole_secs = secs.trunc() as i64;
+            let nanos = (secs.fract() * 1_000_000_000.0) as u32;
+            let naive = NaiveDateTime::from_timestamp_opt(whole_secs, nanos)?;
+            let utc: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+            return Some(DateTime::from(utc));
+        }
+
+        let s = value.as_str()?;
+
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Some(DateTime::from(dt));
+        }
+
+        if let Some(format) = &self.timestamp_format {
+            if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+                let utc: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+                return Some(DateTime::from(utc));
+            }
         }
+
+        None
     }
 
-    pub fn transform_and_print(&self, line: String) -> anyhow::Result<()> {
+    /// Re-serializes a filtered/transformed record as a single compact JSON line, applying
+    /// the same jql/jql_filter projection and excluded-fields rules as the pretty printer.
+    /// Also mirrors the line to `--output-file`, same as the pretty path
+    fn print_json(&mut self, val: &Value, obj: &Map<String, Value>, message: &str, level: &str) -> anyhow::Result<()> {
+        let mut out_obj = Map::new();
+        out_obj.insert(self.message_field.clone(), Value::String(message.to_string()));
+        out_obj.insert(self.level_field.clone(), Value::String(level.to_string()));
+
+        if let Some(ts) = obj.get(&self.timestamp_field) {
+            out_obj.insert(self.timestamp_field.clone(), ts.clone());
+        }
+
+        // Matches the pretty path, which only runs jql/jql_filter and adds extra
+        // fields when extra fields are shown in the first place
+        if !self.hide_extra_fields {
+            if let Some(query) = &self.jql_filter {
+                if jql::walker(val, Some(query)).is_err() {
+                    return Ok(());
+                }
+            }
+
+            let vv = if let Some(query) = &self.jql {
+                let walked = jql::walker(val, Some(query));
+
+                match walked {
+                    Ok(qr) => match qr {
+                        Value::Object(_) => qr,
+                        _ => Value::Object(Map::from_iter(std::iter::once(("_jlf_inner".to_string(), qr)))),
+                    },
+                    Err(why) => match why.as_str() {
+                        "Empty group" => return Ok(()),
+                        _ => {
+                            if why.contains("not found on the parent element") {
+                                return Ok(());
+                            }
+
+                            panic!("{}", why);
+                        }
+                    }
+                }
+            } else {
+                val.clone()
+            };
+
+            if let Some(extra_obj) = vv.as_object() {
+                for (k, v) in extra_obj {
+                    if !self.excluded_fields.contains(k.as_str()) {
+                        out_obj.insert(k.clone(), v.clone());
+                    }
+                }
+            }
+        }
+
+        let rendered = format!("{}\n", Value::Object(out_obj));
+        print!("{}", rendered);
+
+        if let Some(writer) = self.output_file.as_mut() {
+            writer.write_all(rendered.as_bytes())?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn transform_and_print(&mut self, line: String) -> anyhow::Result<()> {
         let json_value = serde_json::from_str::<Value>(&line);
 
         if let Ok(val) = json_value.as_ref() {
@@ -138,7 +561,32 @@ impl LogTransformer {
                         return Ok(());
                     }
 
-                    let time = obj.get(&self.timestamp_field).unwrap().as_f64();
+                    // Skip if this level is below the configured minimum severity
+                    if let Some(min_level) = self.min_level {
+                        if level_rank(level, self.unknown_level_rank) < min_level {
+                            return Ok(());
+                        }
+                    }
+
+                    let grep_target = if self.grep_whole_line { line.as_str() } else { message };
+
+                    if let Some(grep) = &self.grep {
+                        if !grep.is_match(grep_target) {
+                            return Ok(());
+                        }
+                    }
+
+                    if let Some(grep_exclude) = &self.grep_exclude {
+                        if grep_exclude.is_match(grep_target) {
+                            return Ok(());
+                        }
+                    }
+
+                    if self.output_json {
+                        return self.print_json(val, obj, message, level);
+                    }
+
+                    let time = self.resolve_timestamp(obj);
 
                     let bufwtr = BufferWriter::stdout(match self.disable_colors {
                         true => ColorChoice::Never,
@@ -147,36 +595,45 @@ impl LogTransformer {
 
                     let mut buffer = bufwtr.buffer();
 
+                    // Mirrors the terminal buffer's plain text (via NoColor, so ANSI escapes
+                    // never land on disk) into an in-memory buffer. The whole record is flushed
+                    // to the rotating file in a single write below, so a rotation can't split
+                    // one record's bytes across the old and new file
+                    let mut file_buffer = self.output_file.is_some().then(|| NoColor::new(Vec::new()));
+
                     macro_rules! col {
                             ($col:expr) => {
                                 buffer.set_color(ColorSpec::new().set_fg(Some($col)))?;
                             };
                         }
 
-                    if let Some(t) = time {
-                        let parsed_dt = NaiveDateTime::from_timestamp(t as i64, 0);
-                        let datetime: DateTime<Utc> = DateTime::from_utc(parsed_dt, Utc);
-                        let local_dt: DateTime<Local> = DateTime::from(datetime);
+                    macro_rules! out {
+                            ($($arg:tt)*) => {{
+                                write!(&mut buffer, $($arg)*)?;
+                                if let Some(f) = file_buffer.as_mut() {
+                                    write!(f, $($arg)*)?;
+                                }
+                            }};
+                        }
+
+                    if let Some(local_dt) = time {
+                        col!(self.theme.timestamp_color);
 
-                        col!(Color::Magenta);
-                        write!(&mut buffer, "[{}]", local_dt.format("%Y-%m-%d %r"))?;
+                        match &self.timestamp_format {
+                            Some(format) => out!("[{}]", local_dt.format(format)),
+                            None => out!("[{}]", local_dt.format("%Y-%m-%d %r")),
+                        }
                     }
 
-                    col!(match level.trim().to_lowercase().as_str() {
-                            "trace" | "debug" => Color::Black,
-                            "info" | "notice" => Color::Blue,
-                            "warning" => Color::Yellow,
-                            "error" | "err" | "critical" | "crit" | "fatal" | "emerg" | "emergency" | "alert" => Color::Red,
-                            _ => Color::Black
-                        });
+                    col!(self.theme.level_color(level));
 
-                    write!(&mut buffer, "[{}] ", level).unwrap();
+                    out!("[{}] ", level);
 
-                    col!(Color::Black);
-                    write!(&mut buffer, "{}", message).unwrap();
+                    col!(self.theme.value_color);
+                    out!("{}", message);
 
                     if message.len() > MULTILINE_MESSAGE_THRESHOLD && !self.multiline_fields {
-                        write!(&mut buffer, "\n")?;
+                        out!("\n");
                     }
 
                     if !self.hide_extra_fields {
@@ -241,45 +698,52 @@ impl LogTransformer {
 
                         for (k, v) in extra_fields {
                             if self.multiline_fields {
-                                write!(&mut buffer, "\n")?;
-                                write!(&mut buffer, "  ")?;
-                                col!(Color::Green);
-                                write!(&mut buffer, "{}", k)?;
+                                out!("\n");
+                                out!("  ");
+                                col!(self.theme.key_color);
+                                out!("{}", k);
 
                                 if v.len() > MULTILINE_FIELD_THRESHOLD || v.contains("\n") {
-                                    col!(Color::Black);
-                                    write!(&mut buffer, ":\n")?;
-                                    col!(Color::Black);
-                                    write!(&mut buffer, "{}", v)?;
-                                    write!(&mut buffer, "\n")?;
+                                    col!(self.theme.value_color);
+                                    out!(":\n");
+                                    col!(self.theme.value_color);
+                                    out!("{}", v);
+                                    out!("\n");
                                 } else {
-                                    col!(Color::Black);
-                                    write!(&mut buffer, "=")?;
-                                    col!(Color::Black);
-                                    write!(&mut buffer, "{}", v)?;
+                                    col!(self.theme.value_color);
+                                    out!("=");
+                                    col!(self.theme.value_color);
+                                    out!("{}", v);
                                 }
                             } else {
-                                col!(Color::Black);
-                                write!(&mut buffer, " {} ", self.separator)?;
-                                col!(Color::Green);
-                                write!(&mut buffer, "{}", k)?;
-                                col!(Color::Black);
-                                write!(&mut buffer, "=")?;
-                                col!(Color::Black);
-                                write!(&mut buffer, "{}", v)?;
+                                col!(self.theme.value_color);
+                                out!(" {} ", self.separator);
+                                col!(self.theme.key_color);
+                                out!("{}", k);
+                                col!(self.theme.value_color);
+                                out!("=");
+                                col!(self.theme.value_color);
+                                out!("{}", v);
                             }
                         }
                     }
 
 
-                    col!(Color::Black);
-                    write!(&mut buffer, "\n")?;
+                    col!(self.theme.value_color);
+                    out!("\n");
 
                     for _ in 0..self.spacing {
-                        write!(&mut buffer, "\n")?;
+                        out!("\n");
                     }
 
                     bufwtr.print(&buffer)?;
+
+                    if let Some(f) = file_buffer {
+                        if let Some(writer) = self.output_file.as_mut() {
+                            writer.write_all(f.into_inner().as_slice())?;
+                            writer.flush()?;
+                        }
+                    }
                 }
                 None => {
                     if !self.hide_non_json {
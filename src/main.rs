@@ -39,7 +39,7 @@ fn main() {
 
     let stdin = io::stdin();
 
-    let transformer = LogTransformer::new(config);
+    let mut transformer = LogTransformer::new(config);
 
     for line in stdin.lock().lines() {
         let line = line.expect("Could not read from standard in");